@@ -0,0 +1,145 @@
+use std::collections::VecDeque;
+use std::io;
+
+use super::cpu_times::{cpu_times, cpu_times_percpu, CpuTimes};
+
+/// Number of recent percentages retained per core, for sparkline-style
+/// rendering (e.g. mapping each bucket onto one of the eight braille block
+/// glyphs ▁▂▃▄▅▆▇█).
+const HISTORY_SIZE: usize = 8;
+
+/// Collects CPU usage percentages by diffing successive `CpuTimes`
+/// samples against the previous sample, both system-wide and per core.
+///
+/// New struct, not in Python psutil.
+#[derive(Debug, Clone)]
+pub struct CpuPercentCollector {
+    cpu_times: CpuTimes,
+    cpu_times_percpu: Vec<CpuTimes>,
+    history_percpu: Vec<VecDeque<f64>>,
+}
+
+impl CpuPercentCollector {
+    /// Takes the first `/proc/stat` sample, to be diffed against on the
+    /// first call to `cpu_percent()` or `cpu_percent_percpu()`.
+    pub fn new() -> io::Result<CpuPercentCollector> {
+        let cpu_times = cpu_times()?;
+        let cpu_times_percpu = cpu_times_percpu()?;
+        let history_percpu = cpu_times_percpu
+            .iter()
+            .map(|_| VecDeque::from(vec![0.0; HISTORY_SIZE]))
+            .collect();
+
+        Ok(CpuPercentCollector {
+            cpu_times,
+            cpu_times_percpu,
+            history_percpu,
+        })
+    }
+
+    /// Returns system-wide CPU utilization, as a percentage, since the
+    /// last call to this method (or to `new()`).
+    pub fn cpu_percent(&mut self) -> io::Result<f64> {
+        let new_cpu_times = cpu_times()?;
+        let percent = percent_busy(&self.cpu_times, &new_cpu_times);
+        self.cpu_times = new_cpu_times;
+
+        Ok(percent)
+    }
+
+    /// Returns per-core CPU utilization, as percentages, since the last
+    /// call to this method (or to `new()`). Each call also pushes the new
+    /// percentages into every core's recent window, which is read with
+    /// `history_percpu()`; the window itself is not returned here.
+    pub fn cpu_percent_percpu(&mut self) -> io::Result<Vec<f64>> {
+        let new_cpu_times_percpu = cpu_times_percpu()?;
+
+        let percents = self
+            .cpu_times_percpu
+            .iter()
+            .zip(new_cpu_times_percpu.iter())
+            .map(|(old, new)| percent_busy(old, new))
+            .collect::<Vec<f64>>();
+
+        for (history, &percent) in self.history_percpu.iter_mut().zip(percents.iter()) {
+            history.pop_front();
+            history.push_back(percent);
+        }
+
+        self.cpu_times_percpu = new_cpu_times_percpu;
+
+        Ok(percents)
+    }
+
+    /// Returns, for each core, the window of the most recent percentages
+    /// computed by `cpu_percent_percpu()`, oldest first. The window is
+    /// seeded with zeros until enough samples have accumulated.
+    pub fn history_percpu(&self) -> Vec<Vec<f64>> {
+        self.history_percpu
+            .iter()
+            .map(|history| history.iter().copied().collect())
+            .collect()
+    }
+}
+
+/// Percentage of CPU busy time between two samples, as `100 *
+/// busy_delta / total_delta`, clamped to `0.0..=100.0`.
+fn percent_busy(old: &CpuTimes, new: &CpuTimes) -> f64 {
+    let busy_delta = new.busy_ticks().saturating_sub(old.busy_ticks()) as f64;
+    let old_total = old.busy_ticks() + old.idle_total_ticks();
+    let new_total = new.busy_ticks() + new.idle_total_ticks();
+    let total_delta = new_total.saturating_sub(old_total) as f64;
+
+    if total_delta == 0.0 {
+        return 0.0;
+    }
+
+    (100.0 * busy_delta / total_delta).clamp(0.0, 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpu_times(user: u64, idle: u64) -> CpuTimes {
+        CpuTimes {
+            user,
+            nice: 0,
+            system: 0,
+            idle,
+            iowait: 0,
+            irq: 0,
+            softirq: 0,
+            steal: 0,
+            guest: 0,
+            guest_nice: 0,
+        }
+    }
+
+    #[test]
+    fn test_percent_busy_handles_zero_total_delta() {
+        let sample = cpu_times(0, 100);
+
+        assert_eq!(percent_busy(&sample, &sample), 0.0);
+    }
+
+    #[test]
+    fn test_percent_busy_computes_ratio_of_deltas() {
+        let old = cpu_times(0, 100);
+        let new = cpu_times(50, 150);
+
+        // busy grew by 50, total (busy + idle) grew by 100.
+        assert_eq!(percent_busy(&old, &new), 50.0);
+    }
+
+    #[test]
+    fn test_percent_busy_clamps_above_100() {
+        let old = cpu_times(0, 100);
+        let new = cpu_times(300, 50);
+
+        // busy_delta (300) exceeds total_delta (250), which can only
+        // happen with a malformed/inconsistent sample; clamp rather than
+        // report over 100%.
+        assert_eq!(percent_busy(&old, &new), 100.0);
+    }
+}
@@ -1,62 +1,146 @@
-use std::fs;
-use std::io;
+use std::fs::{self, File};
+use std::io::{self, Read};
 use std::str::FromStr;
 use std::time::Duration;
 
-use crate::cpu::os::{linux::CpuTimesExt as _, unix::CpuTimesExt as _};
 use crate::utils::invalid_data;
 use crate::{Count, TICKS_PER_SECOND};
 
-/// Every attribute represents the seconds the CPU has spent in the given mode.
+/// Every attribute represents the number of ticks the CPU has spent in the
+/// given mode, as reported by the kernel. Use the `Duration`-returning
+/// accessors (`user()`, `system()`, ...) to convert these into wall-clock
+/// time, or the `_ticks()` accessors to get at the raw integer counters for
+/// exact delta arithmetic between two samples.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CpuTimes {
-    pub(crate) user: Duration,
-    pub(crate) nice: Duration,
-    pub(crate) system: Duration,
-    pub(crate) idle: Duration,
-    pub(crate) iowait: Duration,
-    pub(crate) irq: Duration,
-    pub(crate) softirq: Duration,
-    pub(crate) steal: Duration,
-    pub(crate) guest: Duration,
-    pub(crate) guest_nice: Duration,
+    pub(crate) user: u64,
+    pub(crate) nice: u64,
+    pub(crate) system: u64,
+    pub(crate) idle: u64,
+    pub(crate) iowait: u64,
+    pub(crate) irq: u64,
+    pub(crate) softirq: u64,
+    pub(crate) steal: u64,
+    pub(crate) guest: u64,
+    pub(crate) guest_nice: u64,
 }
 
 impl CpuTimes {
+    /// Number of scheduler clock ticks per second, the unit `/proc/stat`
+    /// reports times in.
+    pub fn ticks_per_second() -> f64 {
+        *TICKS_PER_SECOND
+    }
+
     /// Time spent by normal processes executing in user mode;
     /// on Linux this also includes guest time.
     pub fn user(&self) -> Duration {
+        ticks_to_duration(self.user)
+    }
+
+    /// Ticks spent by normal processes executing in user mode.
+    pub fn user_ticks(&self) -> u64 {
         self.user
     }
 
     /// Time spent by processes executing in kernel mode.
     pub fn system(&self) -> Duration {
+        ticks_to_duration(self.system)
+    }
+
+    /// Ticks spent by processes executing in kernel mode.
+    pub fn system_ticks(&self) -> u64 {
         self.system
     }
 
     /// Time spent doing nothing.
     pub fn idle(&self) -> Duration {
+        ticks_to_duration(self.idle)
+    }
+
+    /// Ticks spent doing nothing.
+    pub fn idle_ticks(&self) -> u64 {
         self.idle
     }
 
+    /// Ticks spent by niced processes executing in user mode.
+    pub fn nice_ticks(&self) -> u64 {
+        self.nice
+    }
+
+    /// Ticks spent waiting for I/O to complete.
+    pub fn iowait_ticks(&self) -> u64 {
+        self.iowait
+    }
+
+    /// Ticks spent servicing hardware interrupts.
+    pub fn irq_ticks(&self) -> u64 {
+        self.irq
+    }
+
+    /// Ticks spent servicing software interrupts.
+    pub fn softirq_ticks(&self) -> u64 {
+        self.softirq
+    }
+
+    /// Ticks stolen by other operating systems running in a virtualized
+    /// environment.
+    pub fn steal_ticks(&self) -> u64 {
+        self.steal
+    }
+
+    /// Ticks spent running a virtual CPU for guest operating systems.
+    pub fn guest_ticks(&self) -> u64 {
+        self.guest
+    }
+
+    /// Ticks spent running a niced guest.
+    pub fn guest_nice_ticks(&self) -> u64 {
+        self.guest_nice
+    }
+
+    /// Ticks spent idle, including `iowait`.
+    ///
+    /// The kernel (and tools like htop) treat `iowait` as idle time: a CPU
+    /// waiting on I/O is not doing anything, it just happens to have a
+    /// request in flight.
+    pub fn idle_total_ticks(&self) -> u64 {
+        self.idle + self.iowait
+    }
+
+    /// Time spent idle, including `iowait`.
+    pub fn idle_total(&self) -> Duration {
+        ticks_to_duration(self.idle_total_ticks())
+    }
+
+    /// Ticks spent busy, i.e. not idle.
+    ///
+    /// On Linux, `guest` ticks are already folded into `user`, and
+    /// `guest_nice` ticks into `nice`, by the kernel, so they are
+    /// subtracted back out here before summing; otherwise guest time would
+    /// be counted twice.
+    pub fn busy_ticks(&self) -> u64 {
+        let user = self.user.saturating_sub(self.guest);
+        let nice = self.nice.saturating_sub(self.guest_nice);
+
+        user + nice + self.system + self.irq + self.softirq + self.steal
+    }
+
     /// New method, not in Python psutil.
     pub fn busy(&self) -> Duration {
-        // TODO: what about guest and guest_nice?
-        self.user()
-            + self.system()
-            + self.nice()
-            + self.iowait() // TODO: is iowait idle time?
-            + self.irq()
-            + self.softirq()
-            + self.steal()
+        ticks_to_duration(self.busy_ticks())
     }
 
     /// New method, not in Python psutil.
     pub fn total(&self) -> Duration {
-        self.busy() + self.idle()
+        ticks_to_duration(self.busy_ticks() + self.idle_total_ticks())
     }
 }
 
+fn ticks_to_duration(ticks: u64) -> Duration {
+    Duration::from_secs_f64(ticks as f64 / *TICKS_PER_SECOND)
+}
+
 impl FromStr for CpuTimes {
     type Err = std::io::Error;
 
@@ -64,11 +148,8 @@ impl FromStr for CpuTimes {
         let fields = s
             .split_whitespace()
             .skip(1)
-            .map(|entry| Ok(try_parse!(entry, Count::from_str)))
-            .collect::<io::Result<Vec<Count>>>()?
-            .into_iter()
-            .map(|entry| Duration::from_secs_f64(entry as f64 / *TICKS_PER_SECOND))
-            .collect::<Vec<Duration>>();
+            .map(|entry| Ok(try_parse!(entry, Count::from_str) as u64))
+            .collect::<io::Result<Vec<u64>>>()?;
 
         if fields.len() != 10 {
             return Err(invalid_data(&format!(
@@ -105,15 +186,12 @@ impl FromStr for CpuTimes {
 
 pub fn cpu_times() -> io::Result<CpuTimes> {
     let data = fs::read_to_string("/proc/stat")?;
-    let lines = data.lines().collect::<Vec<&str>>();
-
-    if lines.is_empty() {
-        return Err(invalid_data("'/proc/stat' is empty"));
-    }
-
-    let line = lines[0];
+    let line = data
+        .lines()
+        .next()
+        .ok_or_else(|| invalid_data("'/proc/stat' is empty"))?;
 
-    CpuTimes::from_str(&line)
+    CpuTimes::from_str(line)
 }
 
 pub fn cpu_times_percpu() -> io::Result<Vec<CpuTimes>> {
@@ -130,12 +208,77 @@ pub fn cpu_times_percpu() -> io::Result<Vec<CpuTimes>> {
 
     let mut cpu_times = Vec::new();
     for line in lines {
-        cpu_times.push(CpuTimes::from_str(&line)?);
+        cpu_times.push(CpuTimes::from_str(line)?);
     }
 
     Ok(cpu_times)
 }
 
+/// Aggregate and per-core CPU times, read from `/proc/stat` in a single
+/// pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuTimesAll {
+    pub cpu_times: CpuTimes,
+    pub cpu_times_percpu: Vec<CpuTimes>,
+}
+
+/// Reads the aggregate `cpu` line and the per-core `cpuN` lines out of
+/// `/proc/stat` with a single read, instead of the two separate reads
+/// `cpu_times()` and `cpu_times_percpu()` would otherwise cost.
+pub fn cpu_times_all() -> io::Result<CpuTimesAll> {
+    let data = fs::read_to_string("/proc/stat")?;
+
+    parse_cpu_times_all(&data)
+}
+
+fn parse_cpu_times_all(data: &str) -> io::Result<CpuTimesAll> {
+    let mut lines = data.lines();
+
+    let line = lines
+        .next()
+        .ok_or_else(|| invalid_data("'/proc/stat' is empty"))?;
+    let cpu_times = CpuTimes::from_str(line)?;
+
+    let cpu_times_percpu = lines
+        .take_while(|line| line.starts_with("cpu"))
+        .map(CpuTimes::from_str)
+        .collect::<io::Result<Vec<CpuTimes>>>()?;
+
+    if cpu_times_percpu.is_empty() {
+        return Err(invalid_data("'/proc/stat' is missing per cpu times"));
+    }
+
+    Ok(CpuTimesAll {
+        cpu_times,
+        cpu_times_percpu,
+    })
+}
+
+/// Reads `/proc/stat` into a reusable buffer, so that polling in a loop
+/// with `cpu_times_all()` does not allocate a fresh `String` for the file
+/// contents on every iteration. The returned per-core `Vec` is still
+/// allocated anew on each call.
+#[derive(Debug, Default)]
+pub struct CpuTimesReader {
+    buf: String,
+}
+
+impl CpuTimesReader {
+    pub fn new() -> CpuTimesReader {
+        CpuTimesReader::default()
+    }
+
+    /// Reads `/proc/stat` into the reader's buffer, clearing and reusing
+    /// its allocation from the previous call, and parses the aggregate
+    /// and per-core CPU times out of it.
+    pub fn cpu_times_all(&mut self) -> io::Result<CpuTimesAll> {
+        self.buf.clear();
+        File::open("/proc/stat")?.read_to_string(&mut self.buf)?;
+
+        parse_cpu_times_all(&self.buf)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -145,17 +288,74 @@ mod tests {
         let line = "cpu  11867200 6935 2978038 19104017 85955 502109 144021 0 0 0";
         let result = CpuTimes::from_str(line).unwrap();
         let expected = CpuTimes {
-            user: Duration::from_secs_f64(11_867_200_f64 / *TICKS_PER_SECOND),
-            nice: Duration::from_secs_f64(6935_f64 / *TICKS_PER_SECOND),
-            system: Duration::from_secs_f64(2_978_038_f64 / *TICKS_PER_SECOND),
-            idle: Duration::from_secs_f64(19_104_017_f64 / *TICKS_PER_SECOND),
-            iowait: Duration::from_secs_f64(85955_f64 / *TICKS_PER_SECOND),
-            irq: Duration::from_secs_f64(502_109_f64 / *TICKS_PER_SECOND),
-            softirq: Duration::from_secs_f64(144_021_f64 / *TICKS_PER_SECOND),
-            steal: Duration::default(),
-            guest: Duration::default(),
-            guest_nice: Duration::default(),
+            user: 11_867_200,
+            nice: 6935,
+            system: 2_978_038,
+            idle: 19_104_017,
+            iowait: 85955,
+            irq: 502_109,
+            softirq: 144_021,
+            steal: 0,
+            guest: 0,
+            guest_nice: 0,
         };
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_ticks_accessors_match_duration_accessors() {
+        let line = "cpu  11867200 6935 2978038 19104017 85955 502109 144021 0 0 0";
+        let result = CpuTimes::from_str(line).unwrap();
+
+        assert_eq!(result.user_ticks(), 11_867_200);
+        assert_eq!(
+            result.user(),
+            Duration::from_secs_f64(11_867_200_f64 / CpuTimes::ticks_per_second())
+        );
+    }
+
+    #[test]
+    fn test_busy_and_total_exclude_guest_and_count_iowait_as_idle() {
+        let line = "cpu  100 10 50 200 30 5 5 0 20 2";
+        let result = CpuTimes::from_str(line).unwrap();
+
+        // user (100) and nice (10) already include guest (20) and
+        // guest_nice (2), so they must be subtracted back out.
+        let expected_busy_ticks = (100 - 20) + (10 - 2) + 50 + 5 + 5 + 0;
+        assert_eq!(result.busy_ticks(), expected_busy_ticks);
+
+        // idle (200) plus iowait (30).
+        assert_eq!(result.idle_total_ticks(), 230);
+
+        assert_eq!(result.total(), ticks_to_duration(expected_busy_ticks + 230));
+    }
+
+    #[test]
+    fn test_parse_cpu_times_all() {
+        let data = "cpu  11867200 6935 2978038 19104017 85955 502109 144021 0 0 0\n\
+             cpu0 5933600 3467 1489019 9552008 42977 251054 72010 0 0 0\n\
+             cpu1 5933600 3468 1489019 9552009 42978 251055 72011 0 0 0\n\
+             intr 0\n";
+
+        let result = parse_cpu_times_all(data).unwrap();
+
+        assert_eq!(
+            result.cpu_times,
+            CpuTimes::from_str(data.lines().next().unwrap()).unwrap()
+        );
+        assert_eq!(result.cpu_times_percpu.len(), 2);
+    }
+
+    #[test]
+    fn test_cpu_times_reader_reuses_buffer_across_calls() {
+        let mut reader = CpuTimesReader::new();
+        let first = reader.cpu_times_all().unwrap();
+        let second = reader.cpu_times_all().unwrap();
+
+        // The counters are live, so they may have ticked forward between
+        // the two reads; just check that the same reader, with its buffer
+        // cleared and refilled, parses a consistent sample both times.
+        assert_eq!(first.cpu_times_percpu.len(), second.cpu_times_percpu.len());
+        assert!(second.cpu_times.total() >= first.cpu_times.total());
+    }
 }